@@ -8,33 +8,57 @@ use std::{
     fs,
     env,
     error::Error,
+    io::{self, Read, Write},
 };
 
+use regex::RegexBuilder;
+
+#[derive(Debug)]
 pub struct Config {
     pub pattern: String,
-    pub filename: String,
+    pub filenames: Vec<String>,
     pub case_sensitive: bool,
+    pub output: Option<String>,
+    pub recursive: bool,
+    pub regex: bool,
 }
 
 impl Config {
     pub fn new(
         mut args: impl Iterator<Item=String>,
-    ) -> Result<Self, &'static str> {
+    ) -> Result<Self, String> {
         args.next();
 
-        let pattern = match args.next() {
-            Some(p) => p,
-            None => return Err("Didn't get pattern to match"),
-        };
-
-        let filename = match args.next() {
-            Some(f) => f,
-            None => return Err("Didn't get filename"),
-        };
-
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
-
-        Ok(Self { pattern, filename, case_sensitive })
+        let mut pattern = None;
+        let mut filenames = Vec::new();
+        let mut ignore_case = false;
+        let mut output = None;
+        let mut recursive = false;
+        let mut regex = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-i" | "--ignore-case" => ignore_case = true,
+                "-r" | "--recursive" => recursive = true,
+                "-E" | "--regex" => regex = true,
+                "-o" | "--output" => {
+                    let path = args.next()
+                        .ok_or_else(|| format!("{} requires a path", arg))?;
+                    output = Some(path);
+                }
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("Unknown flag: {}", flag));
+                }
+                _ if pattern.is_none() => pattern = Some(arg),
+                _ => filenames.push(arg),
+            }
+        }
+
+        let pattern = pattern.ok_or("Didn't get pattern to match")?;
+
+        let case_sensitive = !ignore_case && env::var("CASE_INSENSITIVE").is_err();
+
+        Ok(Self { pattern, filenames, case_sensitive, output, recursive, regex })
     }
 }
 
@@ -52,7 +76,6 @@ impl Config {
 /// 
 /// assert_eq!(result, vec![(1, "Rust. Effective.")]);
 /// ```
-
 pub fn search<'a>(
     pattern: &str,
     contents: &'a str,
@@ -78,7 +101,6 @@ pub fn search<'a>(
 /// 
 /// assert_eq!(result, vec![(1, "Rust. Effective."), (2, "Without DUST.")]);
 /// ```
-
 pub fn search_case_insensitive<'a>(
     pattern: &str,
     contents: &'a str,
@@ -90,40 +112,187 @@ pub fn search_case_insensitive<'a>(
         .collect()
 }
 
+/// Searches for a regular expression in a string.
+///
+/// # Examples
+///
+/// ```
+/// use gremp::*;
+///
+/// let pattern = "R.st";
+/// let contents = "Rust. Effective.\nWithout DUST.";
+///
+/// let result = search_regex(pattern, contents, true).unwrap();
+///
+/// assert_eq!(result, vec![(1, "Rust. Effective.")]);
+/// ```
+pub fn search_regex<'a>(
+    pattern: &str,
+    contents: &'a str,
+    case_sensitive: bool,
+) -> Result<Vec<(usize, &'a str)>, regex::Error> {
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(!case_sensitive)
+        .build()?;
+
+    Ok(contents.lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line))
+        .filter(|(_, line)| regex.is_match(line))
+        .collect())
+}
+
+/// A match tagged with the path it was found in: `(path, line_no, line)`.
+pub type FileMatch<'a> = (String, usize, &'a str);
+
+/// Searches for a pattern in the contents of a single file, tagging each
+/// match with the path it came from.
+pub fn search_in_file<'a>(
+    path: &str,
+    pattern: &str,
+    contents: &'a str,
+    case_sensitive: bool,
+    regex: bool,
+) -> Result<Vec<FileMatch<'a>>, Box<dyn Error>> {
+    let results = if regex {
+        search_regex(pattern, contents, case_sensitive)?
+    } else if case_sensitive {
+        search(pattern, contents)
+    } else {
+        search_case_insensitive(pattern, contents)
+    };
+
+    Ok(results.into_iter()
+        .map(|(line_no, line)| (path.to_string(), line_no, line))
+        .collect())
+}
+
+/// Expands the given paths into a flat list of file paths, descending into
+/// directories when `recursive` is set. Paths that can't be read are
+/// skipped rather than aborting the whole run.
+fn expand_paths(paths: &[String], recursive: bool) -> Vec<String> {
+    let mut expanded = Vec::new();
+
+    for path in paths {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        if metadata.is_dir() {
+            if recursive {
+                collect_dir(path, &mut expanded);
+            }
+        } else {
+            expanded.push(path.clone());
+        }
+    }
+
+    expanded
+}
+
+fn collect_dir(dir: &str, out: &mut Vec<String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let path = match path.to_str() {
+            Some(path) => path,
+            None => continue,
+        };
+
+        if entry.path().is_dir() {
+            collect_dir(path, out);
+        } else {
+            out.push(path.to_string());
+        }
+    }
+}
+
 /// Searches for a pattern in a file.
 ///
 /// # Examples
-/// 
+///
 /// ```
+/// use std::fs;
 /// use gremp::*;
-/// 
+///
+/// fs::write("gremp_doctest_sample.txt", "a line with pattern in it\n").unwrap();
+///
 /// let args = vec![
 ///     String::from("/path/to/binary"),
 ///     String::from("pattern"),
-///     String::from("sample.txt"),
+///     String::from("gremp_doctest_sample.txt"),
 /// ];
 ///
 /// let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
-///     panic!(err);
+///     panic!("{}", err);
 /// });
 ///
 /// let result = run(&config);
 /// assert!(result.is_ok(), "Should have accepted input");
+///
+/// fs::remove_file("gremp_doctest_sample.txt").unwrap();
 /// ```
-
 pub fn run(
     config: &Config
 ) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(&config.filename)?;
+    let read_stdin = config.filenames.is_empty()
+        || config.filenames.iter().any(|f| f == "-");
+
+    let real_filenames: Vec<String> = config.filenames.iter()
+        .filter(|f| f.as_str() != "-")
+        .cloned()
+        .collect();
+    let paths = expand_paths(&real_filenames, config.recursive);
+
+    let multiple_files = paths.len() + if read_stdin { 1 } else { 0 } > 1;
+
+    let mut lines = Vec::new();
+
+    if read_stdin {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+
+        for (path, line_no, line) in search_in_file("-", &config.pattern, &contents, config.case_sensitive, config.regex)? {
+            if multiple_files {
+                lines.push(format!("{}:{}: {}", path, line_no, line));
+            } else {
+                lines.push(format!("{}. {}", line_no, line));
+            }
+        }
+    }
 
-    let results = if config.case_sensitive {
-        search(&config.pattern, &contents)
-    } else {
-        search_case_insensitive(&config.pattern, &contents)
-    };
+    for path in &paths {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+
+        for (path, line_no, line) in search_in_file(path, &config.pattern, &contents, config.case_sensitive, config.regex)? {
+            if multiple_files {
+                lines.push(format!("{}:{}: {}", path, line_no, line));
+            } else {
+                lines.push(format!("{}. {}", line_no, line));
+            }
+        }
+    }
 
-    for (line_no, line) in results {
-        println!("{}. {}", line_no, line);
+    match &config.output {
+        Some(path) => {
+            let mut file = fs::File::create(path)?;
+            for line in lines {
+                writeln!(file, "{}", line)?;
+            }
+        }
+        None => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
     }
 
     Ok(())
@@ -148,7 +317,8 @@ mod tests {
         ];
 
         let result = Config::new(args.into_iter());
-        assert!(result.is_err(), "Missing file argument");
+        assert!(result.is_ok(), "Missing filename falls back to reading stdin");
+        assert!(result.unwrap().filenames.is_empty());
     }
 
     #[test]
@@ -164,7 +334,7 @@ mod tests {
     }
 
     #[test]
-    fn it_checks_file_existence() {
+    fn it_skips_files_that_do_not_exist() {
         let args = vec![
             String::from("/path/to/binary"),
             String::from("pattern"),
@@ -172,11 +342,11 @@ mod tests {
         ];
 
         let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
-            panic!(err);
+            panic!("{}", err);
         });
 
         let result = run(&config);
-        assert!(result.is_err(), "Specified file does not exist");
+        assert!(result.is_ok(), "Missing files should be skipped, not fatal");
     }
 
     #[test]
@@ -208,33 +378,290 @@ mod tests {
 
     #[test]
     fn it_searches_in_file() {
+        fs::write("gremp_searches_in_file.txt", "Pattern here.\nNothing else.\n").unwrap();
+
         let args = vec![
             String::from("/path/to/binary"),
             String::from("pattern"),
-            String::from("sample.txt"),
+            String::from("gremp_searches_in_file.txt"),
+            String::from("-o"),
+            String::from("gremp_searches_in_file_out.txt"),
         ];
 
         let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
-            panic!(err);
+            panic!("{}", err);
         });
 
         let result = run(&config);
         assert!(result.is_ok(), "Should have accepted input");
+        let written = fs::read_to_string("gremp_searches_in_file_out.txt").unwrap();
+        assert!(written.is_empty(), "Case-sensitive search should not match \"Pattern\"");
+
+        // Search case insensitive via the -i flag
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("pattern"),
+            String::from("gremp_searches_in_file.txt"),
+            String::from("-i"),
+            String::from("-o"),
+            String::from("gremp_searches_in_file_out.txt"),
+        ];
+
+        let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        assert!(!config.case_sensitive, "-i should disable case sensitivity");
+
+        let result = run(&config);
+        assert!(result.is_ok(), "Should have accepted input");
+        let written = fs::read_to_string("gremp_searches_in_file_out.txt").unwrap();
+        assert!(written.contains("Pattern here."));
+
+        fs::remove_file("gremp_searches_in_file.txt").unwrap();
+        fs::remove_file("gremp_searches_in_file_out.txt").unwrap();
+    }
+
+    #[test]
+    fn it_falls_back_to_the_case_insensitive_env_var() {
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("pattern"),
+            String::from("filename"),
+        ];
 
-        // Search case insensitive
         env::set_var("CASE_INSENSITIVE", "1");
 
+        let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        assert!(!config.case_sensitive, "CASE_INSENSITIVE env var should disable case sensitivity");
+
+        env::remove_var("CASE_INSENSITIVE");
+    }
+
+    #[test]
+    fn it_parses_ignore_case_flag_before_or_after_positionals() {
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("--ignore-case"),
+            String::from("pattern"),
+            String::from("filename"),
+        ];
+
+        let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        assert!(!config.case_sensitive);
+        assert_eq!(config.pattern, "pattern");
+        assert_eq!(config.filenames, vec![String::from("filename")]);
+    }
+
+    #[test]
+    fn it_parses_output_flag() {
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("pattern"),
+            String::from("filename"),
+            String::from("-o"),
+            String::from("output.txt"),
+        ];
+
+        let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        assert_eq!(config.output, Some(String::from("output.txt")));
+    }
+
+    #[test]
+    fn it_requires_a_path_after_output_flag() {
         let args = vec![
             String::from("/path/to/binary"),
             String::from("pattern"),
-            String::from("sample.txt"),
+            String::from("filename"),
+            String::from("-o"),
+        ];
+
+        let result = Config::new(args.into_iter());
+        assert!(result.is_err(), "-o without a path should be rejected");
+    }
+
+    #[test]
+    fn it_writes_results_to_the_output_file() {
+        fs::write("gremp_test_input.txt", "a matching pattern line\n").unwrap();
+
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("pattern"),
+            String::from("gremp_test_input.txt"),
+            String::from("-o"),
+            String::from("gremp_test_output.txt"),
         ];
 
         let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
-            panic!(err);
+            panic!("{}", err);
         });
 
         let result = run(&config);
-        assert!(result.is_ok(), "Should have accepted input");
+        assert!(result.is_ok(), "Should have written output");
+
+        let written = fs::read_to_string("gremp_test_output.txt").unwrap();
+        assert!(written.contains("pattern"));
+
+        fs::remove_file("gremp_test_input.txt").unwrap();
+        fs::remove_file("gremp_test_output.txt").unwrap();
+    }
+
+    #[test]
+    fn it_parses_multiple_filenames() {
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("pattern"),
+            String::from("one.txt"),
+            String::from("two.txt"),
+        ];
+
+        let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        assert_eq!(
+            config.filenames,
+            vec![String::from("one.txt"), String::from("two.txt")],
+        );
+    }
+
+    #[test]
+    fn it_searches_multiple_files_and_tags_the_path() {
+        let dir = std::env::temp_dir().join("gremp_multi_file_test");
+        fs::create_dir_all(&dir).unwrap();
+
+        let one = dir.join("one.txt");
+        let two = dir.join("two.txt");
+        fs::write(&one, "duct tape\n").unwrap();
+        fs::write(&two, "no match here\n").unwrap();
+        let out = dir.join("out.txt");
+
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("duct"),
+            one.to_str().unwrap().to_string(),
+            two.to_str().unwrap().to_string(),
+            String::from("-o"),
+            out.to_str().unwrap().to_string(),
+        ];
+
+        let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        let paths = expand_paths(&config.filenames, config.recursive);
+        assert_eq!(paths.len(), 2);
+
+        let result = run(&config);
+        assert!(result.is_ok());
+
+        let written = fs::read_to_string(&out).unwrap();
+        assert_eq!(written, format!("{}:1: duct tape\n", one.to_str().unwrap()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_walks_directories_recursively() {
+        let dir = std::env::temp_dir().join("gremp_recursive_test");
+        let nested = dir.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.join("top.txt"), "duct tape\n").unwrap();
+        fs::write(nested.join("deep.txt"), "more duct tape\n").unwrap();
+
+        let paths = expand_paths(&[dir.to_str().unwrap().to_string()], true);
+        assert_eq!(paths.len(), 2);
+
+        let paths = expand_paths(&[dir.to_str().unwrap().to_string()], false);
+        assert!(paths.is_empty(), "Non-recursive mode should skip directories");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_treats_dash_as_a_stdin_marker() {
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("pattern"),
+            String::from("-"),
+        ];
+
+        let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        assert_eq!(config.filenames, vec![String::from("-")]);
+    }
+
+    #[test]
+    fn it_searches_stdin_contents_directly() {
+        let pattern = "duct";
+        let contents = "Rust:\nSafe, fast, productive.\nPick three.\nDuct tape.";
+
+        assert_eq!(
+            vec![(String::from("-"), 2, "Safe, fast, productive.")],
+            search_in_file("-", pattern, contents, true, false).unwrap(),
+        );
+    }
+
+    #[test]
+    fn it_parses_regex_flag() {
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("--regex"),
+            String::from("d.ct"),
+            String::from("filename"),
+        ];
+
+        let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        assert!(config.regex);
+    }
+
+    #[test]
+    fn it_searches_with_a_regex_pattern() {
+        let pattern = "d.ct";
+        let contents = "Rust:\nSafe, fast, productive.\nPick three.\nDuct tape.";
+
+        assert_eq!(
+            vec![(2, "Safe, fast, productive.")],
+            search_regex(pattern, contents, true).unwrap(),
+        );
+
+        assert_eq!(
+            vec![(2, "Safe, fast, productive."), (4, "Duct tape.")],
+            search_regex(pattern, contents, false).unwrap(),
+        );
+    }
+
+    #[test]
+    fn it_surfaces_invalid_regex_patterns_as_an_error() {
+        let result = search_regex("[", "anything", true);
+        assert!(result.is_err(), "Invalid regex should not panic");
+    }
+
+    #[test]
+    fn it_rejects_unknown_flags() {
+        let args = vec![
+            String::from("/path/to/binary"),
+            String::from("pattern"),
+            String::from("filename"),
+            String::from("--bogus"),
+        ];
+
+        let result = Config::new(args.into_iter());
+        assert!(result.is_err(), "Unknown flag should be rejected");
+        assert!(result.unwrap_err().contains("--bogus"));
     }
 }